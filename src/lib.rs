@@ -26,7 +26,7 @@
 //! ## Example
 //!
 //! ```rust
-//! use line_straddler::{LineGenerator, Line, LineType, Glyph, GlyphStyle, Color};
+//! use line_straddler::{LineGenerator, LineSegment, LineType, Glyph, GlyphStyle, Color};
 //!
 //! # fn draw_line(_point_1: (f32, f32), _point_2: (f32, f32), _style: GlyphStyle) {}
 //! // Take some glyphs from, e.g, cosmic-text
@@ -42,6 +42,9 @@
 //!         width: 2.0,
 //!         x: 0.0,
 //!         style,
+//!         metrics: None,
+//!         ink_x_range: None,
+//!         ink_lowest_y: None,
 //!     },
 //!     Glyph {
 //!         line_y: 0.0,
@@ -49,6 +52,9 @@
 //!         width: 2.0,
 //!         x: 3.0,
 //!         style,
+//!         metrics: None,
+//!         ink_x_range: None,
+//!         ink_lowest_y: None,
 //!     },
 //!     Glyph {
 //!         line_y: 5.0,
@@ -56,6 +62,9 @@
 //!         width: 2.0,
 //!         x: 0.0,
 //!         style,
+//!         metrics: None,
+//!         ink_x_range: None,
+//!         ink_lowest_y: None,
 //!     },
 //!     Glyph {
 //!         line_y: 5.0,
@@ -63,6 +72,9 @@
 //!         width: 2.0,
 //!         x: 3.0,
 //!         style,
+//!         metrics: None,
+//!         ink_x_range: None,
+//!         ink_lowest_y: None,
 //!     },
 //! ];
 //!
@@ -77,16 +89,22 @@
 //! lines.extend(alg.pop_line());
 //!
 //! // Draw all of the lines.
-//! for line in lines {
-//!     let point_1 = (line.start_x, line.y);
-//!     let point_2 = (line.end_x, line.y);
-//!     draw_line(point_1, point_2, line.style);
+//! for segment in lines {
+//!     if let LineSegment::Straight(line) = segment {
+//!         let point_1 = (line.start_x, line.y);
+//!         let point_2 = (line.end_x, line.y);
+//!         draw_line(point_1, point_2, line.style);
+//!     }
 //! }
 //! ```
 
 #![forbid(unsafe_code, future_incompatible, rust_2018_idioms)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::vec::Vec;
+
 /// A glyph to be rendered.
 ///
 /// This corresponds to the [`LayoutGlyph`] type in [`cosmic-text`] and similar types in other text
@@ -95,7 +113,7 @@
 /// [`LayoutGlyph`]: https://docs.rs/cosmic-text/latest/cosmic_text/struct.LayoutGlyph.html
 /// [`cosmic-text`]: https://crates.io/crates/cosmic-text
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub struct Glyph {
+pub struct Glyph<S = GlyphStyle> {
     /// The y coordinate of the glyph's line.
     pub line_y: f32,
 
@@ -109,7 +127,49 @@ pub struct Glyph {
     pub x: f32,
 
     /// The style of the glyph.
-    pub style: GlyphStyle,
+    ///
+    /// This is generic so callers can carry their own per-run payload (link targets, a
+    /// decoration color distinct from the glyph's fill color, shaping data, ...); a run is
+    /// only merged with the next glyph's run when `S` also compares equal.
+    pub style: S,
+
+    /// The line metrics taken from the glyph's font, if known.
+    ///
+    /// When this is `None`, the line generator falls back to estimating the
+    /// position and thickness of decoration lines from `font_size` alone.
+    pub metrics: Option<LineMetrics>,
+
+    /// The horizontal extent of this glyph's visible ink, if known.
+    ///
+    /// Used to gap underlines and strike-throughs around descenders (skip-ink). When this is
+    /// `None`, decoration lines are drawn straight through the glyph as before.
+    pub ink_x_range: Option<(f32, f32)>,
+
+    /// The lowest Y coordinate this glyph's ink reaches down to, if known.
+    ///
+    /// Paired with [`Self::ink_x_range`]; a decoration line is only gapped where this value
+    /// reaches at or past the line's Y coordinate.
+    pub ink_lowest_y: Option<f32>,
+}
+
+/// Font-provided metrics used to place and size decoration lines.
+///
+/// Most font formats (e.g. via `rusttype`, FreeType or the OS/2 table) expose these
+/// values directly; passing them through here means decoration lines sit where the font
+/// designer intended instead of at a guessed fraction of the font size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    /// Distance from the baseline to the top of the underline.
+    pub underline_offset: f32,
+
+    /// Thickness of the underline.
+    pub underline_thickness: f32,
+
+    /// Distance from the baseline to the top of the strikeout line.
+    pub strikeout_offset: f32,
+
+    /// Thickness of the strikeout line.
+    pub strikeout_thickness: f32,
 }
 
 /// Glyph styling information.
@@ -167,7 +227,7 @@ impl Color {
 /// The horizontal line that needs to be rendered.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[non_exhaustive]
-pub struct Line {
+pub struct Line<S = GlyphStyle> {
     /// The Y coordinate of the line.
     pub y: f32,
 
@@ -177,8 +237,82 @@ pub struct Line {
     /// The X coordinate of the line's end.
     pub end_x: f32,
 
+    /// The thickness of the line.
+    pub thickness: f32,
+
+    /// The style of the line.
+    pub style: S,
+}
+
+/// A piece of decoration output emitted by a [`LineGenerator`].
+///
+/// Most [`LineStyle`]s expand a run into one or more straight [`Line`]s; [`LineStyle::Wavy`]
+/// can't be represented that way, so it gets its own variant carrying a sampled path. Renderers
+/// that can only draw straight segments can ignore [`Self::Wavy`] or flatten its points into
+/// their own short line segments.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum LineSegment<S = GlyphStyle> {
+    /// A straight line segment.
+    Straight(Line<S>),
+
+    /// A wavy path, sampled as a sequence of points along a sine wave.
+    Wavy(WavyLine<S>),
+}
+
+impl<S: Copy> LineSegment<S> {
+    /// Get this segment as a straight line, if it is one.
+    #[inline]
+    pub fn as_line(&self) -> Option<Line<S>> {
+        match self {
+            Self::Straight(line) => Some(*line),
+            Self::Wavy(_) => None,
+        }
+    }
+}
+
+/// A wavy decoration line, sampled as a path of points.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct WavyLine<S = GlyphStyle> {
+    /// The sampled points of the wave, in order from the run's start to its end.
+    pub points: Vec<(f32, f32)>,
+
+    /// The thickness the wave should be stroked with.
+    pub thickness: f32,
+
     /// The style of the line.
-    pub style: GlyphStyle,
+    pub style: S,
+}
+
+/// The style of decoration line to draw.
+///
+/// This only changes the geometry produced when a run is flushed; the run-merging logic in
+/// [`LineGenerator::add_glyph`] is unaffected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LineStyle {
+    /// A single solid line.
+    Solid,
+
+    /// Two solid lines, stacked vertically.
+    Double,
+
+    /// A series of small dots.
+    Dotted,
+
+    /// A series of short dashes.
+    Dashed,
+
+    /// A sine wave.
+    Wavy,
+}
+
+impl Default for LineStyle {
+    #[inline]
+    fn default() -> Self {
+        Self::Solid
+    }
 }
 
 /// What kind of lind are we trying to produce?
@@ -196,88 +330,356 @@ pub enum LineType {
 }
 
 impl LineType {
-    /// Get the offset of the line given the font size.
-    fn offset(self, font_size: f32) -> f32 {
-        match self {
-            Self::Overline => 0.0,
-            Self::StrikeThrough => font_size / 2.0,
-            Self::Underline => font_size,
+    /// Get the offset of the line given the font size, preferring font metrics when available.
+    fn offset(self, font_size: f32, metrics: Option<LineMetrics>) -> f32 {
+        match (self, metrics) {
+            (Self::Underline, Some(metrics)) => metrics.underline_offset,
+            (Self::StrikeThrough, Some(metrics)) => metrics.strikeout_offset,
+            (Self::Overline, _) => 0.0,
+            (Self::StrikeThrough, None) => font_size / 2.0,
+            (Self::Underline, None) => font_size,
+        }
+    }
+
+    /// Get the thickness of the line given the font size, preferring font metrics when available.
+    fn thickness(self, font_size: f32, metrics: Option<LineMetrics>) -> f32 {
+        match (self, metrics) {
+            (Self::Underline, Some(metrics)) => metrics.underline_thickness,
+            (Self::StrikeThrough, Some(metrics)) => metrics.strikeout_thickness,
+            _ => font_size * DEFAULT_THICKNESS_FRACTION,
         }
     }
 }
 
+/// Fallback line thickness, as a fraction of the font size, used when a glyph carries no
+/// [`LineMetrics`] and for overlines, which [`LineMetrics`] has no thickness for.
+const DEFAULT_THICKNESS_FRACTION: f32 = 0.05;
+
+/// Factor by which a bold glyph's decoration line thickness is scaled up.
+const BOLD_THICKNESS_SCALE: f32 = 1.5;
+
 /// The generator for lines.
 #[derive(Debug)]
-pub struct LineGenerator {
+pub struct LineGenerator<S = GlyphStyle> {
     /// The line we are currently creating, if any.
-    ongoing_line: Option<OngoingLine>,
+    ongoing_line: Option<OngoingLine<S>>,
 
     /// The type of line we are currently creating.
     line_type: LineType,
+
+    /// The style of geometry to expand a run into when it's flushed.
+    line_style: LineStyle,
+
+    /// The device scale factor, used when `snap` is enabled.
+    scale_factor: f32,
+
+    /// Whether to snap flushed geometry to the device pixel grid.
+    snap: bool,
 }
 
-impl LineGenerator {
+impl<S> LineGenerator<S> {
     /// Create a new, empty line generator.
     #[inline]
     pub fn new(ty: LineType) -> Self {
         Self {
             ongoing_line: None,
             line_type: ty,
+            line_style: LineStyle::default(),
+            scale_factor: 1.0,
+            snap: false,
         }
     }
 
+    /// Set the style of geometry produced when a run is flushed.
+    #[inline]
+    pub fn with_line_style(mut self, style: LineStyle) -> Self {
+        self.line_style = style;
+        self
+    }
+
+    /// Set the device scale factor used to snap flushed geometry to the pixel grid.
+    ///
+    /// Has no effect unless [`Self::with_snap`] is also enabled.
+    #[inline]
+    pub fn with_scale_factor(mut self, scale_factor: f32) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+
+    /// Enable or disable snapping flushed geometry to the device pixel grid.
+    ///
+    /// When enabled, a line's `y` and `thickness` are rounded to whole device pixels (with
+    /// thickness clamped to a minimum of one device pixel so thin lines don't vanish), and its
+    /// `start_x`/`end_x` are snapped to device-pixel boundaries too. Disabled by default, which
+    /// leaves output exactly as it was before this existed.
+    #[inline]
+    pub fn with_snap(mut self, snap: bool) -> Self {
+        self.snap = snap;
+        self
+    }
+}
+
+impl<S: PartialEq + Copy + GlyphBoldness> LineGenerator<S> {
     /// Pop the current line out of the generator.
     #[inline]
-    pub fn pop_line(&mut self) -> Option<Line> {
-        self.ongoing_line.take().map(Into::into)
+    pub fn pop_line(&mut self) -> Vec<LineSegment<S>> {
+        match self.ongoing_line.take() {
+            Some(line) => self.expand_line(line),
+            None => Vec::new(),
+        }
     }
 
     /// Add a new glyph to the generator.
     ///
-    /// Returns a new line if one was created.
+    /// Returns the lines that were flushed: the ongoing run, if the new glyph couldn't be
+    /// merged into it, and the part of the run before the glyph's ink gap, if the glyph's ink
+    /// crosses the decoration line.
     #[inline]
-    pub fn add_glyph(&mut self, glyph: impl Into<Glyph>) -> Option<Line> {
+    pub fn add_glyph(&mut self, glyph: impl Into<Glyph<S>>) -> Vec<LineSegment<S>> {
         self.add_glyph_impl(glyph.into())
     }
 
     #[inline]
-    fn add_glyph_impl(&mut self, glyph: Glyph) -> Option<Line> {
-        // See if we need to start a new line.
-        if let Some(line) = self.ongoing_line.as_mut() {
+    fn add_glyph_impl(&mut self, glyph: Glyph<S>) -> Vec<LineSegment<S>> {
+        let thickness = self.line_thickness(&glyph);
+        let y = glyph.line_y + self.line_type.offset(glyph.font_size, glyph.metrics);
+        let mut flushed = Vec::new();
+
+        // See if we need to start a new line. `y` is compared too (not just `thickness`) so
+        // that a run breaks when adjacent glyphs carry `LineMetrics` with different
+        // `underline_offset`/`strikeout_offset`, not just different thickness.
+        let merges = matches!(&self.ongoing_line, Some(line)
             if approx_eq(line.last_line_y, glyph.line_y)
                 && line.end_x <= glyph.x
                 && approx_eq(line.font_size, glyph.font_size)
                 && line.style == glyph.style
-            {
-                // Just extend the current line.
-                line.end_x = glyph.x + glyph.width;
-                return None;
+                && approx_eq(line.thickness, thickness)
+                && approx_eq(line.y, y));
+
+        if merges {
+            // Just extend the current line.
+            self.ongoing_line.as_mut().unwrap().end_x = glyph.x + glyph.width;
+        } else {
+            // Just start a new line.
+            let mut old_line = self.ongoing_line.replace(OngoingLine {
+                y,
+                last_line_y: glyph.line_y,
+                start_x: glyph.x,
+                end_x: glyph.x + glyph.width,
+                style: glyph.style,
+                font_size: glyph.font_size,
+                thickness,
+            });
+
+            // Make sure the old line ends where the new glyph begins if it's on the same line.
+            if let Some(old_line) = old_line.as_mut() {
+                if approx_eq(old_line.last_line_y, glyph.line_y) {
+                    old_line.end_x = glyph.x;
+                }
+            }
+
+            if let Some(old_line) = old_line {
+                flushed.extend(self.expand_line(old_line));
+            }
+        }
+
+        // If this glyph's ink crosses the decoration line, gap the ongoing run around it, the
+        // same way the run is ended where the next glyph's run begins.
+        if let Some((gap_start, gap_end)) = self.ink_gap(&glyph, thickness) {
+            if let Some(line) = self.ongoing_line.take() {
+                let pre_end = gap_start.clamp(line.start_x, line.end_x);
+                let post_start = gap_end.clamp(line.start_x, line.end_x);
+
+                if pre_end > line.start_x {
+                    flushed.extend(self.expand_line(OngoingLine {
+                        end_x: pre_end,
+                        ..line
+                    }));
+                }
+
+                if post_start < line.end_x {
+                    self.ongoing_line = Some(OngoingLine {
+                        start_x: post_start,
+                        ..line
+                    });
+                }
             }
         }
 
-        // Just start a new line.
-        let mut old_line = self.ongoing_line.replace(OngoingLine {
-            y: glyph.line_y + self.line_type.offset(glyph.font_size),
-            last_line_y: glyph.line_y,
-            start_x: glyph.x,
-            end_x: glyph.x + glyph.width,
-            style: glyph.style,
-            font_size: glyph.font_size,
-        });
-
-        // Make sure the old line ends where the new glyph begins if it's on the same line.
-        if let Some(old_line) = old_line.as_mut() {
-            if approx_eq(old_line.last_line_y, glyph.line_y) {
-                old_line.end_x = glyph.x;
+        flushed
+    }
+
+    /// Compute the horizontal interval, padded by `thickness`, that should be left undrawn
+    /// because this glyph's descender ink crosses the decoration line.
+    fn ink_gap(&self, glyph: &Glyph<S>, thickness: f32) -> Option<(f32, f32)> {
+        if !matches!(self.line_type, LineType::Underline | LineType::StrikeThrough) {
+            return None;
+        }
+
+        let (ink_start, ink_end) = glyph.ink_x_range?;
+        let ink_lowest_y = glyph.ink_lowest_y?;
+        let line_y = glyph.line_y + self.line_type.offset(glyph.font_size, glyph.metrics);
+
+        if ink_lowest_y < line_y {
+            // This glyph's ink doesn't reach down to the decoration line.
+            return None;
+        }
+
+        Some((ink_start - thickness, ink_end + thickness))
+    }
+
+    /// Compute the decoration line thickness for a glyph, scaling up for bold glyphs.
+    fn line_thickness(&self, glyph: &Glyph<S>) -> f32 {
+        let thickness = self.line_type.thickness(glyph.font_size, glyph.metrics);
+        if glyph.style.is_bold() {
+            thickness * BOLD_THICKNESS_SCALE
+        } else {
+            thickness
+        }
+    }
+
+    /// Expand a flushed run into the geometry dictated by `self.line_style`.
+    fn expand_line(&self, line: OngoingLine<S>) -> Vec<LineSegment<S>> {
+        let line: Line<S> = line.into();
+
+        match self.line_style {
+            LineStyle::Solid => alloc::vec![LineSegment::Straight(line)],
+            LineStyle::Double => alloc::vec![
+                LineSegment::Straight(Line {
+                    y: line.y - line.thickness,
+                    ..line
+                }),
+                LineSegment::Straight(Line {
+                    y: line.y + line.thickness,
+                    ..line
+                }),
+            ],
+            LineStyle::Dotted => dash_segments(line, line.thickness, line.thickness),
+            LineStyle::Dashed => dash_segments(line, line.thickness * 3.0, line.thickness * 2.0),
+            LineStyle::Wavy => alloc::vec![LineSegment::Wavy(wavy_path(line))],
+        }
+        .into_iter()
+        .map(|segment| self.snap_segment(segment))
+        .collect()
+    }
+
+    /// Snap a segment's geometry to the device pixel grid, if [`Self::with_snap`] is enabled.
+    fn snap_segment(&self, segment: LineSegment<S>) -> LineSegment<S> {
+        if !self.snap {
+            return segment;
+        }
+
+        match segment {
+            LineSegment::Straight(mut line) => {
+                line.thickness = self.snap_thickness(line.thickness);
+                line.y = self.snap_coord(line.y);
+                line.start_x = self.snap_coord(line.start_x);
+                line.end_x = self.snap_coord(line.end_x);
+                LineSegment::Straight(line)
+            }
+            LineSegment::Wavy(mut wavy) => {
+                wavy.thickness = self.snap_thickness(wavy.thickness);
+                for (x, y) in &mut wavy.points {
+                    *x = self.snap_coord(*x);
+                    *y = self.snap_coord(*y);
+                }
+                LineSegment::Wavy(wavy)
             }
         }
+    }
 
-        old_line.map(Into::into)
+    /// Round a coordinate to the nearest device pixel.
+    fn snap_coord(&self, value: f32) -> f32 {
+        round(value * self.scale_factor) / self.scale_factor
+    }
+
+    /// Round a thickness to the nearest device pixel, clamped to at least one device pixel so a
+    /// sub-pixel-thick line doesn't round away to nothing.
+    fn snap_thickness(&self, thickness: f32) -> f32 {
+        let min_thickness = 1.0 / self.scale_factor;
+        self.snap_coord(thickness).max(min_thickness)
     }
 }
 
-#[derive(Debug)]
-struct OngoingLine {
+/// Subdivide `line` into "on" segments of length `on_len`, separated by gaps of length `off_len`.
+fn dash_segments<S: Copy>(line: Line<S>, on_len: f32, off_len: f32) -> Vec<LineSegment<S>> {
+    let step = on_len + off_len;
+    if on_len <= 0.0 || step <= 0.0 {
+        return alloc::vec![LineSegment::Straight(line)];
+    }
+
+    let mut segments = Vec::new();
+    let mut x = line.start_x;
+    while x < line.end_x {
+        let segment_end = (x + on_len).min(line.end_x);
+        segments.push(LineSegment::Straight(Line {
+            start_x: x,
+            end_x: segment_end,
+            ..line
+        }));
+        x += step;
+    }
+    segments
+}
+
+/// Sample a sine wave across `line`, centered on its baseline `y`.
+fn wavy_path<S: Copy>(line: Line<S>) -> WavyLine<S> {
+    const SAMPLES_PER_WAVELENGTH: u32 = 8;
+
+    let wavelength = line.thickness * 6.0;
+    let amplitude = line.thickness;
+
+    let mut points = Vec::new();
+    if wavelength > 0.0 {
+        let step = wavelength / SAMPLES_PER_WAVELENGTH as f32;
+        let mut x = line.start_x;
+        while x < line.end_x {
+            points.push((x, wave_y(line.y, amplitude, wavelength, x - line.start_x)));
+            x += step;
+        }
+    }
+    points.push((
+        line.end_x,
+        wave_y(line.y, amplitude, wavelength, line.end_x - line.start_x),
+    ));
+
+    WavyLine {
+        points,
+        thickness: line.thickness,
+        style: line.style,
+    }
+}
+
+/// The y coordinate of a sine wave of the given `wavelength` and `amplitude` at `x` along the run.
+fn wave_y(baseline_y: f32, amplitude: f32, wavelength: f32, x: f32) -> f32 {
+    if wavelength <= 0.0 {
+        return baseline_y;
+    }
+    baseline_y + amplitude * sin(x / wavelength * TAU)
+}
+
+/// Tau, i.e. two times pi.
+const TAU: f32 = core::f32::consts::PI * 2.0;
+
+/// Whether a glyph style represents bold text, for the purposes of decoration line thickness.
+///
+/// [`LineGenerator::add_glyph`] and [`LineGenerator::pop_line`] require the style type to
+/// implement this, so a custom style type passed to [`LineGenerator`] must implement it too.
+/// Bold runs get thicker decoration lines, the same way they do for [`GlyphStyle`].
+pub trait GlyphBoldness {
+    /// Is this style bold?
+    fn is_bold(&self) -> bool;
+}
+
+impl GlyphBoldness for GlyphStyle {
+    #[inline]
+    fn is_bold(&self) -> bool {
+        self.bold
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OngoingLine<S> {
     /// The Y coordinate of the line.
     y: f32,
 
@@ -287,8 +689,11 @@ struct OngoingLine {
     /// The current X coordinate of the line's end.
     end_x: f32,
 
+    /// The thickness of the line so far.
+    thickness: f32,
+
     /// The style of the line so far.
-    style: GlyphStyle,
+    style: S,
 
     /// The line y of the last glyph we observed.
     last_line_y: f32,
@@ -297,12 +702,13 @@ struct OngoingLine {
     font_size: f32,
 }
 
-impl From<OngoingLine> for Line {
-    fn from(line: OngoingLine) -> Self {
+impl<S> From<OngoingLine<S>> for Line<S> {
+    fn from(line: OngoingLine<S>) -> Self {
         Self {
             y: line.y,
             start_x: line.start_x,
             end_x: line.end_x,
+            thickness: line.thickness,
             style: line.style,
         }
     }
@@ -339,4 +745,18 @@ fn abs(a: f32) -> f32 {
     )
 }
 
+/// Sine of a float.
+fn sin(a: f32) -> f32 {
+    float_switch!(
+        a => [a.sin()] [libm::sinf(a)]
+    )
+}
+
+/// Round a float to the nearest integer.
+fn round(a: f32) -> f32 {
+    float_switch!(
+        a => [a.round()] [libm::roundf(a)]
+    )
+}
+
 const EPSILON: f32 = 0.001;
@@ -20,7 +20,10 @@
 // Public License along with `line-straddler`. If not, see <https://www.gnu.org/licenses/>.
 
 use approx_eq::assert_approx_eq;
-use line_straddler::{Color, Glyph, GlyphStyle, LineGenerator, LineType};
+use line_straddler::{
+    Color, Glyph, GlyphBoldness, GlyphStyle, LineGenerator, LineMetrics, LineSegment, LineStyle,
+    LineType,
+};
 
 #[test]
 fn lines() {
@@ -37,6 +40,9 @@ fn lines() {
             width: 2.0,
             x: 0.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 0.0,
@@ -44,6 +50,9 @@ fn lines() {
             width: 2.0,
             x: 3.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 5.0,
@@ -51,6 +60,9 @@ fn lines() {
             width: 2.0,
             x: 0.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 5.0,
@@ -58,6 +70,9 @@ fn lines() {
             width: 2.0,
             x: 3.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
     ];
 
@@ -76,6 +91,7 @@ fn lines() {
             lines.extend(alg.add_glyph(glyph));
         }
         lines.extend(alg.pop_line());
+        let lines: Vec<_> = lines.iter().map(LineSegment::as_line).map(Option::unwrap).collect();
 
         assert_eq!(lines.len(), 2);
         assert_approx_eq!(lines[0].y as f64, first_line_y);
@@ -107,6 +123,9 @@ fn mid_line_switch() {
             width: 2.0,
             x: 0.0,
             style: style1,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 0.0,
@@ -114,6 +133,9 @@ fn mid_line_switch() {
             width: 2.0,
             x: 3.0,
             style: style1,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 0.0,
@@ -121,6 +143,9 @@ fn mid_line_switch() {
             width: 2.0,
             x: 6.0,
             style: style2,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 0.0,
@@ -128,6 +153,9 @@ fn mid_line_switch() {
             width: 2.0,
             x: 9.0,
             style: style2,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
     ];
 
@@ -138,6 +166,7 @@ fn mid_line_switch() {
         lines.extend(alg.add_glyph(glyph));
     }
     lines.extend(alg.pop_line());
+    let lines: Vec<_> = lines.iter().map(LineSegment::as_line).map(Option::unwrap).collect();
 
     assert_eq!(lines.len(), 2);
     assert_approx_eq!(lines[0].y as f64, 0.0);
@@ -166,6 +195,9 @@ fn full_line_then_switch() {
             width: 17.828125,
             x: 0.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 3.2000008,
@@ -173,6 +205,9 @@ fn full_line_then_switch() {
             width: 8.890625,
             x: 17.828125,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 3.2000008,
@@ -180,6 +215,9 @@ fn full_line_then_switch() {
             width: 20.28125,
             x: 26.71875,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 3.2000008,
@@ -187,6 +225,9 @@ fn full_line_then_switch() {
             width: 19.6875,
             x: 47.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 3.2000008,
@@ -194,6 +235,9 @@ fn full_line_then_switch() {
             width: 10.171875,
             x: 66.6875,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 3.2000008,
@@ -201,6 +245,9 @@ fn full_line_then_switch() {
             width: 26.8125,
             x: 76.859375,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 3.2000008,
@@ -208,6 +255,9 @@ fn full_line_then_switch() {
             width: 20.359375,
             x: 103.671875,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 35.2,
@@ -215,6 +265,9 @@ fn full_line_then_switch() {
             width: 17.828125,
             x: 0.0,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
         Glyph {
             line_y: 35.2,
@@ -222,6 +275,9 @@ fn full_line_then_switch() {
             width: 8.890625,
             x: 17.828125,
             style,
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
         },
     ];
 
@@ -240,6 +296,7 @@ fn full_line_then_switch() {
             lines.extend(alg.add_glyph(glyph));
         }
         lines.extend(alg.pop_line());
+        let lines: Vec<_> = lines.iter().map(LineSegment::as_line).map(Option::unwrap).collect();
 
         assert!(
             (lines[0].start_x - lines[0].end_x).abs() > 0.0001,
@@ -263,5 +320,342 @@ fn colors() {
 fn other_coverage() {
     println!("{:?}", Color::default().clone());
     assert_eq!(Color::default(), Color::default());
-    println!("{:?}", LineGenerator::new(LineType::Overline));
+    println!("{:?}", LineGenerator::<GlyphStyle>::new(LineType::Overline));
+}
+
+#[test]
+fn metrics_override_offset_and_thickness() {
+    let metrics = LineMetrics {
+        underline_offset: 12.0,
+        underline_thickness: 1.25,
+        strikeout_offset: 6.0,
+        strikeout_thickness: 0.75,
+    };
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+
+    let glyphs = [
+        Glyph {
+            line_y: 0.0,
+            font_size: 10.0,
+            width: 4.0,
+            x: 0.0,
+            style,
+            metrics: Some(metrics),
+            ink_x_range: None,
+            ink_lowest_y: None,
+        },
+        Glyph {
+            line_y: 0.0,
+            font_size: 10.0,
+            width: 4.0,
+            x: 4.0,
+            style,
+            metrics: Some(metrics),
+            ink_x_range: None,
+            ink_lowest_y: None,
+        },
+    ];
+
+    let mut alg = LineGenerator::new(LineType::Underline);
+    let mut lines = vec![];
+    for glyph in glyphs {
+        lines.extend(alg.add_glyph(glyph));
+    }
+    lines.extend(alg.pop_line());
+
+    assert_eq!(lines.len(), 1);
+    let line = lines[0].as_line().unwrap();
+    assert_approx_eq!(line.y as f64, 12.0);
+    assert_approx_eq!(line.thickness as f64, 1.25);
+    assert_approx_eq!(line.end_x as f64, 8.0);
+}
+
+#[test]
+fn bold_glyph_scales_thickness() {
+    let metrics = LineMetrics {
+        underline_offset: 12.0,
+        underline_thickness: 1.0,
+        strikeout_offset: 6.0,
+        strikeout_thickness: 0.75,
+    };
+    let style = GlyphStyle {
+        bold: true,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let glyph = Glyph {
+        line_y: 0.0,
+        font_size: 10.0,
+        width: 4.0,
+        x: 0.0,
+        style,
+        metrics: Some(metrics),
+        ink_x_range: None,
+        ink_lowest_y: None,
+    };
+
+    let mut alg = LineGenerator::new(LineType::Underline);
+    let mut lines = vec![];
+    lines.extend(alg.add_glyph(glyph));
+    lines.extend(alg.pop_line());
+
+    assert_eq!(lines.len(), 1);
+    let line = lines[0].as_line().unwrap();
+    // Bold runs scale thickness up by `BOLD_THICKNESS_SCALE` (1.5x).
+    assert_approx_eq!(line.thickness as f64, 1.5);
+}
+
+#[test]
+fn differing_metrics_break_run() {
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let metrics_a = LineMetrics {
+        underline_offset: 8.0,
+        underline_thickness: 1.0,
+        strikeout_offset: 4.0,
+        strikeout_thickness: 1.0,
+    };
+    let metrics_b = LineMetrics {
+        underline_offset: 10.0,
+        underline_thickness: 1.0,
+        strikeout_offset: 4.0,
+        strikeout_thickness: 1.0,
+    };
+
+    let glyphs = [
+        Glyph {
+            line_y: 0.0,
+            font_size: 10.0,
+            width: 4.0,
+            x: 0.0,
+            style,
+            metrics: Some(metrics_a),
+            ink_x_range: None,
+            ink_lowest_y: None,
+        },
+        Glyph {
+            line_y: 0.0,
+            font_size: 10.0,
+            width: 4.0,
+            x: 4.0,
+            style,
+            metrics: Some(metrics_b),
+            ink_x_range: None,
+            ink_lowest_y: None,
+        },
+    ];
+
+    // Same thickness, different `underline_offset` -- the run must still break, since the two
+    // glyphs would otherwise be drawn at the wrong (first glyph's) `y`.
+    let mut alg = LineGenerator::new(LineType::Underline);
+    let mut lines = vec![];
+    for glyph in glyphs {
+        lines.extend(alg.add_glyph(glyph));
+    }
+    lines.extend(alg.pop_line());
+
+    assert_eq!(lines.len(), 2);
+    assert_approx_eq!(lines[0].as_line().unwrap().y as f64, 8.0);
+    assert_approx_eq!(lines[1].as_line().unwrap().y as f64, 10.0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ExtraStyle(u32);
+
+impl GlyphBoldness for ExtraStyle {
+    fn is_bold(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn generic_style_breaks_run_on_difference() {
+    let glyphs = [
+        Glyph {
+            line_y: 0.0,
+            font_size: 10.0,
+            width: 4.0,
+            x: 0.0,
+            style: ExtraStyle(1),
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
+        },
+        Glyph {
+            line_y: 0.0,
+            font_size: 10.0,
+            width: 4.0,
+            x: 4.0,
+            style: ExtraStyle(2),
+            metrics: None,
+            ink_x_range: None,
+            ink_lowest_y: None,
+        },
+    ];
+
+    let mut alg = LineGenerator::<ExtraStyle>::new(LineType::Overline);
+    let mut lines = vec![];
+    for glyph in glyphs {
+        lines.extend(alg.add_glyph(glyph));
+    }
+    lines.extend(alg.pop_line());
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].as_line().unwrap().style, ExtraStyle(1));
+    assert_eq!(lines[1].as_line().unwrap().style, ExtraStyle(2));
+}
+
+#[test]
+fn dashed_style_subdivides_run() {
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let glyph = Glyph {
+        line_y: 0.0,
+        font_size: 10.0,
+        width: 10.0,
+        x: 0.0,
+        style,
+        metrics: None,
+        ink_x_range: None,
+        ink_lowest_y: None,
+    };
+
+    let mut alg = LineGenerator::new(LineType::Underline).with_line_style(LineStyle::Dashed);
+    let mut lines = vec![];
+    lines.extend(alg.add_glyph(glyph));
+    lines.extend(alg.pop_line());
+
+    // thickness = 0.5, so on = 1.5, off = 1.0, step = 2.5 over a run of length 10.
+    assert_eq!(lines.len(), 4);
+    let first = lines[0].as_line().unwrap();
+    assert_approx_eq!(first.start_x as f64, 0.0);
+    assert_approx_eq!(first.end_x as f64, 1.5);
+}
+
+#[test]
+fn dotted_style_subdivides_run() {
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let glyph = Glyph {
+        line_y: 0.0,
+        font_size: 10.0,
+        width: 10.0,
+        x: 0.0,
+        style,
+        metrics: None,
+        ink_x_range: None,
+        ink_lowest_y: None,
+    };
+
+    let mut alg = LineGenerator::new(LineType::Underline).with_line_style(LineStyle::Dotted);
+    let mut lines = vec![];
+    lines.extend(alg.add_glyph(glyph));
+    lines.extend(alg.pop_line());
+
+    // thickness = 0.5, so on = off = 0.5, step = 1.0 over a run of length 10.
+    assert_eq!(lines.len(), 10);
+}
+
+#[test]
+fn wavy_style_emits_a_sampled_path() {
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let glyph = Glyph {
+        line_y: 0.0,
+        font_size: 10.0,
+        width: 10.0,
+        x: 0.0,
+        style,
+        metrics: None,
+        ink_x_range: None,
+        ink_lowest_y: None,
+    };
+
+    let mut alg = LineGenerator::new(LineType::Underline).with_line_style(LineStyle::Wavy);
+    let mut lines = vec![];
+    lines.extend(alg.add_glyph(glyph));
+    lines.extend(alg.pop_line());
+
+    assert_eq!(lines.len(), 1);
+    match &lines[0] {
+        LineSegment::Wavy(wavy) => assert!(wavy.points.len() >= 2),
+        _ => panic!("expected a wavy path"),
+    }
+}
+
+#[test]
+fn skip_ink_gaps_underline_around_descender() {
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let glyph = Glyph {
+        line_y: 0.0,
+        font_size: 10.0,
+        width: 10.0,
+        x: 0.0,
+        style,
+        metrics: None,
+        ink_x_range: Some((2.0, 4.0)),
+        ink_lowest_y: Some(12.0),
+    };
+
+    let mut alg = LineGenerator::new(LineType::Underline);
+    let mut lines = vec![];
+    lines.extend(alg.add_glyph(glyph));
+    lines.extend(alg.pop_line());
+
+    // The run is split around the ink interval, padded by the line's thickness (0.5) on
+    // either side: [0.0, 1.5) and (4.5, 10.0].
+    assert_eq!(lines.len(), 2);
+    let first = lines[0].as_line().unwrap();
+    let second = lines[1].as_line().unwrap();
+    assert_approx_eq!(first.start_x as f64, 0.0);
+    assert_approx_eq!(first.end_x as f64, 1.5);
+    assert_approx_eq!(second.start_x as f64, 4.5);
+    assert_approx_eq!(second.end_x as f64, 10.0);
+}
+
+#[test]
+fn pixel_snapping_rounds_and_clamps_thickness() {
+    let style = GlyphStyle {
+        bold: false,
+        color: Color::rgba(0, 0, 0, 255),
+    };
+    let glyph = Glyph {
+        line_y: 3.3,
+        font_size: 2.0,
+        width: 1.3,
+        x: 0.0,
+        style,
+        metrics: None,
+        ink_x_range: None,
+        ink_lowest_y: None,
+    };
+
+    let mut alg = LineGenerator::new(LineType::Underline)
+        .with_scale_factor(2.0)
+        .with_snap(true);
+    let mut lines = vec![];
+    lines.extend(alg.add_glyph(glyph));
+    lines.extend(alg.pop_line());
+
+    assert_eq!(lines.len(), 1);
+    let line = lines[0].as_line().unwrap();
+    // y = 3.3 + font_size(2.0) = 5.3, snapped to the nearest half-pixel at scale 2.0.
+    assert_approx_eq!(line.y as f64, 5.5);
+    // end_x = 1.3, snapped to the nearest half-pixel at scale 2.0.
+    assert_approx_eq!(line.end_x as f64, 1.5);
+    // thickness = font_size * 0.05 = 0.1, clamped up to one device pixel (0.5 at scale 2.0).
+    assert_approx_eq!(line.thickness as f64, 0.5);
 }